@@ -2,11 +2,13 @@
 //! GET  /{uuid} -> shields.io
 
 use std::collections::HashMap;
+use std::future::Future;
+use std::time::Duration;
 
 use axum::{
     Router,
-    extract::{FromRef, FromRequestParts, Path, Query},
-    http::{Response, StatusCode, request::Parts},
+    extract::{FromRef, Path, Query, State},
+    http::{HeaderMap, Method, Response, StatusCode, header},
     response::IntoResponse,
     routing::get,
 };
@@ -14,12 +16,65 @@ use uuid::Uuid;
 
 use shields::builder::Badge;
 
+mod migrations;
+
 use bb8::{Pool, PooledConnection};
 use bb8_postgres::PostgresConnectionManager;
 use tokio_postgres::NoTls;
+use tokio_postgres::Socket;
+use tokio_postgres::tls::{MakeTlsConnect, TlsConnect};
 
 // Taken from https://github.com/tokio-rs/axum/blob/main/examples/tokio-postgres/src/main.rs
-type ConnectionPool = Pool<PostgresConnectionManager<NoTls>>;
+type ConnectionPool<Tls> = Pool<PostgresConnectionManager<Tls>>;
+
+#[derive(Clone)]
+struct AppState<Tls>
+where
+    Tls: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+    Tls::Stream: Send + Sync,
+{
+    pool: ConnectionPool<Tls>,
+    retry: RetryConfig,
+}
+
+impl<Tls> FromRef<AppState<Tls>> for ConnectionPool<Tls>
+where
+    Tls: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+    Tls::Stream: Send + Sync,
+{
+    fn from_ref(state: &AppState<Tls>) -> Self {
+        state.pool.clone()
+    }
+}
+
+impl<Tls> FromRef<AppState<Tls>> for RetryConfig
+where
+    Tls: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+    Tls::Stream: Send + Sync,
+{
+    fn from_ref(state: &AppState<Tls>) -> Self {
+        state.retry
+    }
+}
+
+/// Retry policy for transient (connection-level) Postgres failures.
+///
+/// Backoff is exponential starting at `base_backoff`, doubling on each
+/// attempt and capped at `max_backoff`.
+#[derive(Clone, Copy)]
+struct RetryConfig {
+    max_retries: u32,
+    base_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl RetryConfig {
+    fn backoff_for(&self, attempt: u32) -> Duration {
+        self.base_backoff
+            .saturating_mul(1 << attempt.min(16))
+            .min(self.max_backoff)
+    }
+}
 
 // also stolen
 /// Utility function for mapping any error into a `500 Internal Server Error`
@@ -31,24 +86,85 @@ where
     (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
 }
 
-// stolen once more!
-// we can also write a custom extractor that grabs a connection from the pool
-// which setup is appropriate depends on your application
-struct DatabaseConnection(PooledConnection<'static, PostgresConnectionManager<NoTls>>);
+/// Classifies a `tokio_postgres` error as safe to retry on a fresh
+/// connection (the connection was dropped, or never reached the server) or
+/// fatal (the server rejected the query itself, e.g. a constraint
+/// violation or bad input).
+fn is_retryable(err: &tokio_postgres::Error) -> bool {
+    if err.is_closed() {
+        return true;
+    }
 
-impl<S> FromRequestParts<S> for DatabaseConnection
-where
-    ConnectionPool: FromRef<S>,
-    S: Send + Sync,
-{
-    type Rejection = (StatusCode, String);
+    err.source()
+        .map(|source| source.downcast_ref::<std::io::Error>().is_some())
+        .unwrap_or(false)
+}
+
+/// An operation's error, tagged with whether `with_retry` may replay it on
+/// a fresh connection. Most errors can defer to [`is_retryable`] via the
+/// `From` impl below, but some operations (e.g. a transaction that has
+/// already issued `COMMIT`) know more than a blanket connection-state check
+/// does and need to force `Fatal` even when the underlying error looks like
+/// a dropped connection.
+enum OpError {
+    Retryable(tokio_postgres::Error),
+    Fatal(tokio_postgres::Error),
+}
 
-    async fn from_request_parts(_parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
-        let pool = ConnectionPool::from_ref(state);
+impl From<tokio_postgres::Error> for OpError {
+    fn from(err: tokio_postgres::Error) -> Self {
+        if is_retryable(&err) {
+            OpError::Retryable(err)
+        } else {
+            OpError::Fatal(err)
+        }
+    }
+}
 
-        let conn = pool.get_owned().await.map_err(internal_error)?;
+/// Acquires a fresh connection from the pool, mapping pool exhaustion /
+/// connect failures into a `500`.
+async fn acquire<Tls>(
+    pool: &ConnectionPool<Tls>,
+) -> Result<PooledConnection<'static, PostgresConnectionManager<Tls>>, (StatusCode, String)>
+where
+    Tls: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+    Tls::Stream: Send + Sync,
+    Tls::TlsConnect: Send,
+    <Tls::TlsConnect as TlsConnect<Socket>>::Future: Send,
+{
+    pool.get_owned().await.map_err(internal_error)
+}
 
-        Ok(Self(conn))
+/// Runs `op` against a fresh connection, retrying on retryable errors with
+/// exponential backoff up to `retry.max_retries` times.
+async fn with_retry<Tls, T, F, Fut>(
+    pool: &ConnectionPool<Tls>,
+    retry: &RetryConfig,
+    mut op: F,
+) -> Result<T, (StatusCode, String)>
+where
+    Tls: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+    Tls::Stream: Send + Sync,
+    Tls::TlsConnect: Send,
+    <Tls::TlsConnect as TlsConnect<Socket>>::Future: Send,
+    F: FnMut(PooledConnection<'static, PostgresConnectionManager<Tls>>) -> Fut,
+    Fut: Future<Output = Result<T, OpError>>,
+{
+    let mut attempt = 0;
+
+    loop {
+        let conn = acquire(pool).await?;
+
+        match op(conn).await {
+            Ok(value) => return Ok(value),
+            Err(OpError::Retryable(_err)) if attempt < retry.max_retries => {
+                tokio::time::sleep(retry.backoff_for(attempt)).await;
+                attempt += 1;
+            }
+            Err(OpError::Retryable(err)) | Err(OpError::Fatal(err)) => {
+                return Err(internal_error(err));
+            }
+        }
     }
 }
 
@@ -63,17 +179,23 @@ fn badge_style_from_string(s: &str) -> Option<shields::BadgeStyle> {
     }
 }
 
-async fn new_uuid(
-    DatabaseConnection(conn): DatabaseConnection,
-) -> Result<String, (StatusCode, String)> {
-    let uuid: Uuid = conn
-        .query_one(
+async fn new_uuid<Tls>(State(state): State<AppState<Tls>>) -> Result<String, (StatusCode, String)>
+where
+    Tls: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+    Tls::Stream: Send + Sync,
+    Tls::TlsConnect: Send,
+    <Tls::TlsConnect as TlsConnect<Socket>>::Future: Send,
+{
+    let uuid: Uuid = with_retry(&state.pool, &state.retry, |conn| async move {
+        conn.query_one(
             "INSERT INTO counts (id, count) VALUES (gen_random_uuid(), 0) RETURNING id",
             &[],
         )
         .await
-        .map_err(internal_error)?
-        .get("id");
+        .map(|row| row.get("id"))
+        .map_err(OpError::from)
+    })
+    .await?;
 
     Ok(format!(
         r#"Welcome! This is a simple API for generating visitor count badges using shields.io.
@@ -85,39 +207,100 @@ You can customize the badge appearance using any of the query parameters support
 https://shields.io/badges/static-badge
 
 Note: Only query parameters are supported.
-      `logoSize`, `cacheSeconds`, and `link` are not supported.
-      The default value for label is "visitors""#,
+      `logoSize` and `link` are not supported.
+      `cacheSeconds` sets the `Cache-Control: max-age` on the badge response.
+      The default value for label is "visitors"
+
+By default, HEAD requests and requests from GitHub's Camo image proxy don't
+increment your count. To change that, visit:
+/{0}/settings?skipPrefetchIncrement=false"#,
         uuid.to_string()
     ))
 }
 
-async fn get_badge(
-    Path(uuid): Path<Uuid>,
-    Query(params): Query<HashMap<String, String>>,
-    DatabaseConnection(conn): DatabaseConnection,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let result = conn
+/// Increments the count for `uuid` inside an explicit transaction.
+///
+/// Retrying is only safe for errors that happen *before* `COMMIT` is sent -
+/// the transaction aborts server-side and nothing was persisted, so a
+/// replay on a fresh connection can't double-increment. Once `commit()` has
+/// been issued, though, a dropped connection doesn't tell us whether the
+/// server actually applied it before going away, so any error from
+/// `commit()` itself is always treated as [`OpError::Fatal`] and propagated
+/// rather than replayed, even though it may look like an ordinary
+/// connection-closed error to [`is_retryable`].
+///
+/// When `is_prefetch` is set (a `HEAD` request or a known caching-proxy
+/// user agent) and the uuid has `skip_prefetch_increment` enabled, the
+/// count is left untouched so proxy warm-ups don't inflate it.
+async fn increment_count<Tls>(
+    mut conn: PooledConnection<'static, PostgresConnectionManager<Tls>>,
+    uuid: Uuid,
+    is_prefetch: bool,
+) -> Result<Option<i64>, OpError>
+where
+    Tls: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+    Tls::Stream: Send + Sync,
+{
+    let txn = conn.transaction().await.map_err(OpError::from)?;
+
+    let result = txn
         .query_opt(
             r#"
             UPDATE counts
-            SET count = count + 1
+            SET count = CASE
+                WHEN $2 AND skip_prefetch_increment THEN count
+                ELSE count + 1
+            END
             WHERE id = $1
             RETURNING count
             "#,
-            &[&uuid],
+            &[&uuid, &is_prefetch],
         )
         .await
-        .map_err(internal_error)?;
+        .map_err(OpError::from)?;
 
-    let current_count: i64 = match result {
-        Some(row) => row.get("count"),
-        None => {
-            return Err((StatusCode::NOT_FOUND, "UUID not found".to_string()));
-        }
-    };
+    // A failure here can no longer be classified by connection state alone:
+    // the server may have already committed before the failure surfaced, so
+    // replaying this transaction could double-increment. Always propagate.
+    txn.commit().await.map_err(OpError::Fatal)?;
+
+    Ok(result.map(|row| row.get("count")))
+}
+
+/// Builds the `ETag` for a badge response from its uuid and current count,
+/// so the value changes exactly when the rendered badge would.
+fn etag_for(uuid: Uuid, count: i64) -> String {
+    format!("\"{uuid}-{count}\"")
+}
 
-    let current_count = current_count.to_string();
+/// Checks an `If-None-Match` header value (which may carry a comma
+/// separated list of weak or strong validators) against `etag`.
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match
+        .split(',')
+        .map(|value| value.trim().trim_start_matches("W/"))
+        .any(|value| value == etag)
+}
+
+/// Heuristically detects prefetch / cache-warmup requests: a `HEAD` probe,
+/// or GitHub's Camo image proxy, recognised by a `User-Agent` header that
+/// contains "camo" (case-insensitive). Other proxies are not recognised.
+fn is_prefetch_request(method: &Method, headers: &HeaderMap) -> bool {
+    if *method == Method::HEAD {
+        return true;
+    }
+
+    headers
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(|ua| ua.to_lowercase().contains("camo"))
+        .unwrap_or(false)
+}
 
+/// Renders a shields.io badge SVG, applying the `style`/`label`/`logo`/
+/// `*Color` query params shared by every badge-producing route.
+/// `default_label` is used when the caller didn't pass `label`.
+fn build_badge_svg(params: &HashMap<String, String>, default_label: &str, message: &str) -> String {
     let badge_style = params
         .get("style")
         .and_then(|badge| badge_style_from_string(badge))
@@ -125,14 +308,9 @@ async fn get_badge(
 
     let mut badge = Badge::style(badge_style);
 
-    badge.label(
-        params
-            .get("label")
-            .map(|x| x.as_str())
-            .unwrap_or("visitors"),
-    );
+    badge.label(params.get("label").map(|x| x.as_str()).unwrap_or(default_label));
 
-    badge.message(&current_count);
+    badge.message(message);
 
     if let Some(logo) = params.get("logo") {
         badge.logo(logo);
@@ -150,56 +328,356 @@ async fn get_badge(
         badge.message_color(message_color);
     }
 
-    Response::builder()
+    badge.build()
+}
+
+/// Checks an `If-None-Match` header against `etag`, so callers can decide
+/// whether a write is even necessary before making one.
+fn is_not_modified(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| etag_matches(value, etag))
+}
+
+/// Shared response tail for any route that renders a per-uuid count as a
+/// badge: sets `ETag`/`Cache-Control` and short-circuits to `304` when the
+/// client's `If-None-Match` already matches `count`.
+fn badge_response(
+    params: &HashMap<String, String>,
+    headers: &HeaderMap,
+    uuid: Uuid,
+    count: i64,
+) -> Result<Response<String>, (StatusCode, String)> {
+    let etag = etag_for(uuid, count);
+
+    let mut response = Response::builder().header("ETag", &etag);
+
+    if let Some(max_age) = params.get("cacheSeconds").and_then(|v| v.parse::<u64>().ok()) {
+        response = response.header("Cache-Control", format!("max-age={max_age}"));
+    }
+
+    if is_not_modified(headers, &etag) {
+        return response
+            .status(StatusCode::NOT_MODIFIED)
+            .body(String::new())
+            .map_err(internal_error);
+    }
+
+    response
         .header("Content-Type", "image/svg+xml")
-        .body(badge.build())
+        .body(build_badge_svg(params, "visitors", &count.to_string()))
         .map_err(internal_error)
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    use dotenvy::EnvLoader;
+/// Reads the current count for `uuid` without mutating it.
+async fn fetch_count<Tls>(
+    conn: PooledConnection<'static, PostgresConnectionManager<Tls>>,
+    uuid: Uuid,
+) -> Result<Option<i64>, OpError>
+where
+    Tls: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+    Tls::Stream: Send + Sync,
+{
+    conn.query_opt("SELECT count FROM counts WHERE id = $1", &[&uuid])
+        .await
+        .map(|row| row.map(|row| row.get::<_, i64>("count")))
+        .map_err(OpError::from)
+}
 
-    let env = EnvLoader::new().load()?;
+async fn get_badge<Tls>(
+    Path(uuid): Path<Uuid>,
+    Query(params): Query<HashMap<String, String>>,
+    method: Method,
+    headers: HeaderMap,
+    State(state): State<AppState<Tls>>,
+) -> Result<impl IntoResponse, (StatusCode, String)>
+where
+    Tls: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+    Tls::Stream: Send + Sync,
+    Tls::TlsConnect: Send,
+    <Tls::TlsConnect as TlsConnect<Socket>>::Future: Send,
+{
+    // Conditional requests only: a plain visit has no `If-None-Match` to
+    // possibly match, so there's nothing to save by reading first - go
+    // straight to the incrementing path and avoid a second round-trip on
+    // the hot (uncached) path. Only pay for the extra read when a proxy is
+    // actually revalidating a cached copy.
+    if headers.get(header::IF_NONE_MATCH).is_some() {
+        let existing_count = with_retry(&state.pool, &state.retry, |conn| async move {
+            fetch_count(conn, uuid).await
+        })
+        .await?;
+
+        let existing_count: i64 = match existing_count {
+            Some(count) => count,
+            None => {
+                return Err((StatusCode::NOT_FOUND, "UUID not found".to_string()));
+            }
+        };
+
+        if is_not_modified(&headers, &etag_for(uuid, existing_count)) {
+            return badge_response(&params, &headers, uuid, existing_count);
+        }
+    }
+
+    let is_prefetch = is_prefetch_request(&method, &headers);
+
+    let current_count = with_retry(&state.pool, &state.retry, |conn| async move {
+        increment_count(conn, uuid, is_prefetch).await
+    })
+    .await?;
+
+    let current_count: i64 = match current_count {
+        Some(count) => count,
+        None => {
+            return Err((StatusCode::NOT_FOUND, "UUID not found".to_string()));
+        }
+    };
+
+    badge_response(&params, &headers, uuid, current_count)
+}
+
+/// `GET /{uuid}/count` - reads the current count without incrementing it,
+/// so badges can be inspected without inflating them.
+async fn get_count<Tls>(
+    Path(uuid): Path<Uuid>,
+    Query(params): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    State(state): State<AppState<Tls>>,
+) -> Result<impl IntoResponse, (StatusCode, String)>
+where
+    Tls: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+    Tls::Stream: Send + Sync,
+    Tls::TlsConnect: Send,
+    <Tls::TlsConnect as TlsConnect<Socket>>::Future: Send,
+{
+    let count = with_retry(&state.pool, &state.retry, |conn| async move {
+        fetch_count(conn, uuid).await
+    })
+    .await?;
+
+    let count = match count {
+        Some(count) => count,
+        None => {
+            return Err((StatusCode::NOT_FOUND, "UUID not found".to_string()));
+        }
+    };
+
+    badge_response(&params, &headers, uuid, count)
+}
+
+/// `GET /{uuid}/settings?skipPrefetchIncrement=<bool>` - the configuration
+/// knob for `skip_prefetch_increment`: lets a uuid opt in or out of
+/// ignoring `HEAD`/caching-proxy requests when incrementing its count.
+async fn update_settings<Tls>(
+    Path(uuid): Path<Uuid>,
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<AppState<Tls>>,
+) -> Result<String, (StatusCode, String)>
+where
+    Tls: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+    Tls::Stream: Send + Sync,
+    Tls::TlsConnect: Send,
+    <Tls::TlsConnect as TlsConnect<Socket>>::Future: Send,
+{
+    let skip_prefetch_increment: bool = params
+        .get("skipPrefetchIncrement")
+        .and_then(|v| v.parse().ok())
+        .ok_or((
+            StatusCode::BAD_REQUEST,
+            "missing or invalid `skipPrefetchIncrement` query parameter (expected `true` or `false`)"
+                .to_string(),
+        ))?;
+
+    let updated = with_retry(&state.pool, &state.retry, |conn| async move {
+        conn.execute(
+            "UPDATE counts SET skip_prefetch_increment = $2 WHERE id = $1",
+            &[&uuid, &skip_prefetch_increment],
+        )
+        .await
+        .map_err(OpError::from)
+    })
+    .await?;
+
+    if updated == 0 {
+        return Err((StatusCode::NOT_FOUND, "UUID not found".to_string()));
+    }
+
+    Ok(format!(
+        "skip_prefetch_increment for {uuid} is now {skip_prefetch_increment}"
+    ))
+}
+
+/// `GET /stats` - service-wide totals: how many uuids are registered and
+/// how many visits have been recorded across all of them. Rendered as JSON
+/// by default, or as a badge with `?format=badge`.
+async fn get_stats<Tls>(
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<AppState<Tls>>,
+) -> Result<impl IntoResponse, (StatusCode, String)>
+where
+    Tls: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+    Tls::Stream: Send + Sync,
+    Tls::TlsConnect: Send,
+    <Tls::TlsConnect as TlsConnect<Socket>>::Future: Send,
+{
+    let (uuids, total_visits): (i64, i64) =
+        with_retry(&state.pool, &state.retry, |conn| async move {
+            conn.query_one(
+                "SELECT COUNT(*), COALESCE(SUM(count), 0)::BIGINT FROM counts",
+                &[],
+            )
+            .await
+            .map(|row| (row.get(0), row.get(1)))
+            .map_err(OpError::from)
+        })
+        .await?;
 
+    if params.get("format").map(|f| f == "badge").unwrap_or(false) {
+        return Response::builder()
+            .header("Content-Type", "image/svg+xml")
+            .body(build_badge_svg(
+                &params,
+                "total visits",
+                &total_visits.to_string(),
+            ))
+            .map_err(internal_error)
+            .map(IntoResponse::into_response);
+    }
+
+    Ok(axum::Json(Stats { uuids, total_visits }).into_response())
+}
+
+#[derive(serde::Serialize)]
+struct Stats {
+    uuids: i64,
+    total_visits: i64,
+}
+
+/// Builds the rustls client config used when `POSTGRESQL_TLS` (or a
+/// `sslmode=require` connection URL) opts into encrypted connections. Trusts
+/// the standard webpki CA roots, same as most managed Postgres providers
+/// expect.
+fn rustls_client_config() -> rustls::ClientConfig {
+    let mut roots = rustls::RootCertStore::empty();
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+
+    rustls::ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth()
+}
+
+fn tls_requested(env: &dotenvy::Env, connection_url: &str) -> bool {
+    env.var("POSTGRESQL_TLS")
+        .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+        .unwrap_or(false)
+        || connection_url.contains("sslmode=require")
+}
+
+/// Builds the pool, bootstraps the schema and serves the app. Generic over
+/// the TLS connector so the same code path runs whether or not encryption
+/// is enabled.
+async fn run<Tls>(
+    env: dotenvy::Env,
+    connection_url: &str,
+    tls: Tls,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    Tls: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+    Tls::Stream: Send + Sync,
+    Tls::TlsConnect: Send,
+    <Tls::TlsConnect as TlsConnect<Socket>>::Future: Send,
+{
     println!("(1/3) Connecting...");
-    let manager = PostgresConnectionManager::new_from_stringlike(
-        &env.var("POSTGRESQL_CONNECTION_URL")?,
-        NoTls,
-    )
-    .unwrap();
+    let manager = PostgresConnectionManager::new_from_stringlike(connection_url, tls).unwrap();
+
+    let mut pool_builder = Pool::builder().max_size(
+        env.var("DB_POOL_MAX_SIZE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or_else(|| num_cpus::get() as u32 * 4),
+    );
+
+    if let Some(min_idle) = env
+        .var("DB_POOL_MIN_IDLE")
+        .ok()
+        .and_then(|v| v.parse().ok())
+    {
+        pool_builder = pool_builder.min_idle(Some(min_idle));
+    }
+
+    if let Some(connection_timeout) = env
+        .var("DB_POOL_CONNECTION_TIMEOUT_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+    {
+        pool_builder = pool_builder.connection_timeout(Duration::from_secs(connection_timeout));
+    }
 
-    let pool = Pool::builder().build(manager).await.unwrap();
+    if let Some(idle_timeout) = env
+        .var("DB_POOL_IDLE_TIMEOUT_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+    {
+        pool_builder = pool_builder.idle_timeout(Some(Duration::from_secs(idle_timeout)));
+    }
+
+    let pool = pool_builder.build(manager).await.unwrap();
+
+    let retry = RetryConfig {
+        max_retries: env
+            .var("DB_MAX_RETRIES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(3),
+        base_backoff: Duration::from_millis(
+            env.var("DB_RETRY_BASE_BACKOFF_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(50),
+        ),
+        max_backoff: Duration::from_millis(200),
+    };
 
     println!("(2/3) Init DB");
     {
         println!("  (1/2) Connecting to pool");
-        let conn = pool.get().await?;
-        println!("  (2/2) Executing init");
-        conn.execute(
-            r#"
-          CREATE TABLE IF NOT EXISTS counts (
-                id UUID PRIMARY KEY,
-                count BIGINT NOT NULL DEFAULT 0
-          );"#,
-            &[],
-        )
-        .await?;
+        let mut conn = pool.get().await?;
+        println!("  (2/2) Running migrations");
+        migrations::run(&mut conn).await?;
         // drop db
     }
 
     println!("(3/3) Starting app");
     let app = Router::new()
         .route("/{uuid}", get(get_badge))
+        .route("/{uuid}/count", get(get_count))
+        .route("/{uuid}/settings", get(update_settings))
+        .route("/stats", get(get_stats))
         .route("/", get(new_uuid))
-        .with_state(pool);
+        .with_state(AppState { pool, retry });
 
-    let listener = tokio::net::TcpListener::bind(env.var("HOST")?)
-        .await
-        .unwrap();
+    let listener = tokio::net::TcpListener::bind(env.var("HOST")?).await.unwrap();
     println!("Ready");
 
     axum::serve(listener, app).await?;
 
     Ok(())
 }
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    use dotenvy::EnvLoader;
+
+    let env = EnvLoader::new().load()?;
+
+    let connection_url = env.var("POSTGRESQL_CONNECTION_URL")?;
+
+    if tls_requested(&env, &connection_url) {
+        let connector = tokio_postgres_rustls::MakeRustlsConnect::new(rustls_client_config());
+        run(env, &connection_url, connector).await
+    } else {
+        run(env, &connection_url, NoTls).await
+    }
+}
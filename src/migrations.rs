@@ -0,0 +1,59 @@
+//! Embedded, versioned SQL migrations.
+//!
+//! Each entry in `MIGRATIONS` is compiled into the binary with
+//! `include_str!` and runs at most once, tracked by name in the
+//! `_migrations` table. This lets the schema evolve across deploys (new
+//! columns, indexes, etc.) without manual DB surgery - add a new ordered
+//! `.sql` file under `migrations/` and list it here.
+
+use bb8::PooledConnection;
+use bb8_postgres::PostgresConnectionManager;
+use tokio_postgres::Socket;
+use tokio_postgres::tls::MakeTlsConnect;
+
+const MIGRATIONS: &[(&str, &str)] = &[
+    ("0001_init", include_str!("../migrations/0001_init.sql")),
+    (
+        "0002_skip_prefetch_increment",
+        include_str!("../migrations/0002_skip_prefetch_increment.sql"),
+    ),
+];
+
+/// Applies every migration in `MIGRATIONS` that isn't yet recorded in
+/// `_migrations`, each inside its own transaction, in order.
+pub async fn run<Tls>(
+    conn: &mut PooledConnection<'_, PostgresConnectionManager<Tls>>,
+) -> Result<(), tokio_postgres::Error>
+where
+    Tls: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+    Tls::Stream: Send + Sync,
+{
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS _migrations (
+            name TEXT PRIMARY KEY,
+            applied_at TIMESTAMPTZ NOT NULL DEFAULT now()
+        )"#,
+        &[],
+    )
+    .await?;
+
+    for (name, sql) in MIGRATIONS {
+        let already_applied = conn
+            .query_opt("SELECT 1 FROM _migrations WHERE name = $1", &[name])
+            .await?
+            .is_some();
+
+        if already_applied {
+            continue;
+        }
+
+        let txn = conn.transaction().await?;
+        txn.batch_execute(sql).await?;
+        txn.execute("INSERT INTO _migrations (name) VALUES ($1)", &[name])
+            .await?;
+        txn.commit().await?;
+    }
+
+    Ok(())
+}